@@ -0,0 +1,258 @@
+//! `StaticModulus64` 上での数論変換（NTT）による畳み込み、および
+//! NTT-friendly でない `DynamicModulus` のための多素数 NTT + Garner 復元。
+
+use crate::modulus::{mod_pow, DynamicModulus, StaticModulus64};
+use crate::{ModInt, Modulus};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+type Mint<const M: u64> = ModInt<u64, StaticModulus64<M>>;
+
+/// `root[k]` と `iroot[k]`（位数 `2^k` の原始根とその逆元）のテーブル。
+type RootTables = (Vec<u64>, Vec<u64>);
+
+thread_local! {
+    static ROOT_CACHE: RefCell<HashMap<u64, RootTables>> = RefCell::new(HashMap::new());
+}
+
+/// `root[k]` が位数 `2^k` の原始根、`iroot[k]` がその逆元であるようなテーブルを返す。
+/// モジュラスごとにキャッシュされる。
+fn root_tables<const M: u64>() -> RootTables {
+    ROOT_CACHE.with(|cache| {
+        if let Some(tables) = cache.borrow().get(&M) {
+            return tables.clone();
+        }
+        let g = StaticModulus64::<M>::primitive_root();
+        let rank2 = (M - 1).trailing_zeros() as usize;
+        let mut root = vec![0u64; rank2 + 1];
+        let mut iroot = vec![0u64; rank2 + 1];
+        root[rank2] = mod_pow(g, (M - 1) >> rank2, M);
+        iroot[rank2] = mod_pow(root[rank2], M - 2, M);
+        for i in (0..rank2).rev() {
+            root[i] = (root[i + 1] as u128 * root[i + 1] as u128 % M as u128) as u64;
+            iroot[i] = (iroot[i + 1] as u128 * iroot[i + 1] as u128 % M as u128) as u64;
+        }
+        let tables = (root, iroot);
+        cache.borrow_mut().insert(M, tables.clone());
+        tables
+    })
+}
+
+/// ビット反転の並び替えを行う。ボトムアップの蝶形演算を行う前に必要な前処理。
+fn bit_reverse(a: &mut [u64]) {
+    let n = a.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            a.swap(i, j);
+        }
+    }
+}
+
+/// 長さが 2 冪の `a` を（逆）変換する。
+fn ntt<const M: u64>(a: &mut [u64], inverse: bool) {
+    let n = a.len();
+    let log = n.trailing_zeros() as usize;
+    let (root, iroot) = root_tables::<M>();
+    bit_reverse(a);
+    for i in 0..log {
+        let block = 1usize << i;
+        let w_base = if inverse { iroot[i + 1] } else { root[i + 1] };
+        for chunk_start in (0..n).step_by(block * 2) {
+            let mut w = 1u64;
+            for j in 0..block {
+                let x = a[chunk_start + j];
+                let y = (a[chunk_start + block + j] as u128 * w as u128 % M as u128) as u64;
+                a[chunk_start + j] = (x + y) % M;
+                a[chunk_start + block + j] = (x + M - y) % M;
+                w = (w as u128 * w_base as u128 % M as u128) as u64;
+            }
+        }
+    }
+    if inverse {
+        let n_inv = mod_pow(n as u64, M - 2, M);
+        for x in a.iter_mut() {
+            *x = (*x as u128 * n_inv as u128 % M as u128) as u64;
+        }
+    }
+}
+
+fn convolution_raw<const M: u64>(mut fa: Vec<u64>, mut fb: Vec<u64>, n: usize) -> Vec<u64> {
+    let len = n.next_power_of_two();
+    fa.resize(len, 0);
+    fb.resize(len, 0);
+    ntt::<M>(&mut fa, false);
+    ntt::<M>(&mut fb, false);
+    for i in 0..len {
+        fa[i] = (fa[i] as u128 * fb[i] as u128 % M as u128) as u64;
+    }
+    ntt::<M>(&mut fa, true);
+    fa.truncate(n);
+    fa
+}
+
+/// 生の `u64` 係数列を modulo `M` で畳み込む。`M` は NTT-friendly な素数である必要がある。
+pub fn convolution_u64<const M: u64>(a: &[u64], b: &[u64]) -> Vec<u64> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let n = a.len() + b.len() - 1;
+    convolution_raw::<M>(a.to_vec(), b.to_vec(), n)
+}
+
+/// `ModInt<u64, StaticModulus64<M>>` の列同士を畳み込む。`M` は NTT-friendly な素数である必要がある。
+pub fn convolution<const M: u64>(a: &[Mint<M>], b: &[Mint<M>]) -> Vec<Mint<M>> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let va: Vec<u64> = a.iter().map(|x| *x.value()).collect();
+    let vb: Vec<u64> = b.iter().map(|x| *x.value()).collect();
+    convolution_u64::<M>(&va, &vb)
+        .into_iter()
+        .map(|x| Mint::<M>::new(x, StaticModulus64::<M>))
+        .collect()
+}
+
+// NTT-friendly でない `DynamicModulus<u64>` 向けの畳み込みに使う 3 つの固定素数。
+const CRT_PRIMES: [u64; 3] = [167772161, 469762049, 998244353];
+
+/// `DynamicModulus<u64>`（10^9+7 など NTT-friendly でない法を含む）上の
+/// `ModInt` 列同士を畳み込む。`CRT_PRIMES` 上で NTT を行い、Garner のアルゴリズムで復元する。
+pub fn convolution_any_mod(
+    a: &[ModInt<u64, DynamicModulus<u64>>],
+    b: &[ModInt<u64, DynamicModulus<u64>>],
+) -> Vec<ModInt<u64, DynamicModulus<u64>>> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let modulus = *a[0].modulus();
+    let m = modulus.modulus();
+
+    assert!(
+        a.iter().chain(b.iter()).all(|x| *x.modulus() == modulus),
+        "mod mismatch"
+    );
+
+    let va: Vec<u64> = a.iter().map(|x| *x.value()).collect();
+    let vb: Vec<u64> = b.iter().map(|x| *x.value()).collect();
+
+    let [p0, p1, p2] = CRT_PRIMES;
+    // 各 CRT 素数で NTT を行う前に、その素数未満まで係数を縮約しておく。
+    // `ntt` の蝶形演算は入力が `M` 未満であることを前提に `x + y` と `x + M - y` を u64 で
+    // 計算するため、`m` が大きく係数が u64 の上限近くにあるとオーバーフローしうる。
+    let reduce = |v: &[u64], p: u64| -> Vec<u64> { v.iter().map(|&x| x % p).collect() };
+    let r0 = convolution_u64::<167772161>(&reduce(&va, p0), &reduce(&vb, p0));
+    let r1 = convolution_u64::<469762049>(&reduce(&va, p1), &reduce(&vb, p1));
+    let r2 = convolution_u64::<998244353>(&reduce(&va, p2), &reduce(&vb, p2));
+
+    // Garner のアルゴリズム: p0, p1, p2 を法とする剰余から各係数を復元する。
+    let inv_p0_mod_p1 = DynamicModulus::new(p1).inv(p0 % p1);
+    let p0p1_mod_p2 = (p0 as u128 * p1 as u128 % p2 as u128) as u64;
+    let inv_p0p1_mod_p2 = DynamicModulus::new(p2).inv(p0p1_mod_p2);
+
+    let n = r0.len();
+    let mut result = Vec::with_capacity(n);
+    for i in 0..n {
+        let x0 = r0[i];
+
+        let diff1 = (r1[i] + p1 - x0 % p1) % p1;
+        let x1 = (diff1 as u128 * inv_p0_mod_p1 as u128 % p1 as u128) as u64;
+
+        let diff2 = (r2[i] + p2 - x0 % p2) % p2;
+        let x1_p0_mod_p2 = (x1 as u128 * p0 as u128 % p2 as u128) as u64;
+        let diff2b = (diff2 + p2 - x1_p0_mod_p2) % p2;
+        let x2 = (diff2b as u128 * inv_p0p1_mod_p2 as u128 % p2 as u128) as u64;
+
+        let value = (x0 as u128
+            + x1 as u128 * p0 as u128
+            + x2 as u128 * (p0 as u128 * p1 as u128))
+            % m as u128;
+        result.push(ModInt::new(value as u64, modulus));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_convolution(a: &[u64], b: &[u64], m: u64) -> Vec<u64> {
+        if a.is_empty() || b.is_empty() {
+            return vec![];
+        }
+        let mut result = vec![0u64; a.len() + b.len() - 1];
+        for (i, &x) in a.iter().enumerate() {
+            for (j, &y) in b.iter().enumerate() {
+                result[i + j] =
+                    ((result[i + j] as u128 + x as u128 * y as u128) % m as u128) as u64;
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn convolution_matches_naive() {
+        const M: u64 = 998244353;
+        let a: Vec<u64> = vec![1, 2, 3, 4, 5];
+        let b: Vec<u64> = vec![6, 7, 8];
+        let got = convolution_u64::<M>(&a, &b);
+        let want = naive_convolution(&a, &b, M);
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn convolution_matches_naive_for_large_prime() {
+        // M が 2^32 を超えると、u64 の蝶形演算は (M-1)^2 でオーバーフローしうる。
+        const M: u64 = 4179340454199820289;
+        let a: Vec<u64> = vec![4179340454199820000, 123456789012345, 1];
+        let b: Vec<u64> = vec![4179340454199820100, 987654321098765, 2];
+        let got = convolution_u64::<M>(&a, &b);
+        let want = naive_convolution(&a, &b, M);
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn convolution_any_mod_matches_naive() {
+        // 10^9+7 は NTT-friendly でないため、Garner 復元のパスを通す。
+        let m = 1_000_000_007u64;
+        let modulus = DynamicModulus::new(m);
+        let a_raw: Vec<u64> = vec![500000003, 123456789, 1, 999999999];
+        let b_raw: Vec<u64> = vec![500000004, 987654321, 2, 123];
+        let a: Vec<_> = a_raw.iter().map(|&x| ModInt::new(x, modulus)).collect();
+        let b: Vec<_> = b_raw.iter().map(|&x| ModInt::new(x, modulus)).collect();
+        let got = convolution_any_mod(&a, &b);
+        let want = naive_convolution(&a_raw, &b_raw, m);
+        let got_values: Vec<u64> = got.into_iter().map(|x| *x.value()).collect();
+        assert_eq!(got_values, want);
+    }
+
+    #[test]
+    fn convolution_any_mod_handles_large_modulus() {
+        // m が CRT 素数より大きいので係数もそれらの素数を超える。事前の縮約と
+        // Garner 復元を経て、法 m での正しい値が得られることを確認する。
+        let m = 1_000_000_000_000u64;
+        let modulus = DynamicModulus::new(m);
+        let a_raw: Vec<u64> = vec![123456789012];
+        let b_raw: Vec<u64> = vec![987654321098];
+        let a: Vec<_> = a_raw.iter().map(|&x| ModInt::new(x, modulus)).collect();
+        let b: Vec<_> = b_raw.iter().map(|&x| ModInt::new(x, modulus)).collect();
+        let got = convolution_any_mod(&a, &b);
+        let want = naive_convolution(&a_raw, &b_raw, m);
+        let got_values: Vec<u64> = got.into_iter().map(|x| *x.value()).collect();
+        assert_eq!(got_values, want);
+    }
+
+    #[test]
+    #[should_panic(expected = "mod mismatch")]
+    fn convolution_any_mod_panics_on_mismatched_modulus() {
+        let a = vec![ModInt::new(1, DynamicModulus::new(1_000_000_007u64))];
+        let b = vec![ModInt::new(1, DynamicModulus::new(998_244_353u64))];
+        convolution_any_mod(&a, &b);
+    }
+}
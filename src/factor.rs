@@ -0,0 +1,198 @@
+//! Pollard のロー法による素因数分解・約数列挙。
+
+use crate::modulus::is_prime;
+
+fn mul_mod(x: u64, y: u64, n: u64) -> u64 {
+    (x as u128 * y as u128 % n as u128) as u64
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+/// Brent の高速化を伴う Pollard のロー法で `n` の自明でない約数を 1 つ見つける。
+/// `n` は合成数であると仮定する。
+///
+/// バッチ化した gcd が `n` に潰れた（= このバッチの中で 2 つ以上の素因数に
+/// 同時にヒットした）場合は、バッチの先頭までさかのぼって 1 ステップずつ
+/// gcd を取り直し、本当の約数を特定する。これを省略すると、バッチ全体が
+/// 無駄になって `c` を変えて最初からやり直すことになり、運が悪いと
+/// 終了しなくなる。
+fn pollard_rho(n: u64) -> u64 {
+    if n & 1 == 0 {
+        return 2;
+    }
+    let mut c: u64 = 1;
+    loop {
+        let f = |x: u64| (mul_mod(x, x, n) + c) % n;
+        let (mut x, mut y, mut ys) = (2u64, 2u64, 2u64);
+        let (mut g, mut q, mut r) = (1u64, 1u64, 1u64);
+        while g == 1 {
+            x = y;
+            for _ in 0..r {
+                y = f(y);
+            }
+            let mut k = 0;
+            while k < r && g == 1 {
+                ys = y;
+                let batch = 128.min(r - k);
+                for _ in 0..batch {
+                    y = f(y);
+                    q = mul_mod(q, x.abs_diff(y), n);
+                }
+                g = gcd(q, n);
+                k += batch;
+            }
+            r *= 2;
+        }
+        if g == n {
+            loop {
+                ys = f(ys);
+                g = gcd(x.abs_diff(ys), n);
+                if g != 1 {
+                    break;
+                }
+            }
+        }
+        if g != n {
+            return g;
+        }
+        c += 1;
+    }
+}
+
+fn factor_rec(n: u64, factors: &mut Vec<u64>) {
+    if n == 1 {
+        return;
+    }
+    if is_prime(n) {
+        factors.push(n);
+        return;
+    }
+    let d = pollard_rho(n);
+    factor_rec(d, factors);
+    factor_rec(n / d, factors);
+}
+
+/// `n` を素因数分解し、`(素数, 指数)` の列を昇順で返す。
+///
+/// `n == 0` は素因数分解できないため panic する。
+pub fn prime_factor(mut n: u64) -> Vec<(u64, u32)> {
+    assert!(n > 0, "prime_factor(0) is undefined");
+    let mut result = Vec::new();
+    for p in [2, 3, 5] {
+        if n.is_multiple_of(p) {
+            let mut e = 0;
+            while n.is_multiple_of(p) {
+                n /= p;
+                e += 1;
+            }
+            result.push((p, e));
+        }
+    }
+    if n > 1 {
+        let mut factors = Vec::new();
+        factor_rec(n, &mut factors);
+        factors.sort_unstable();
+        let mut i = 0;
+        while i < factors.len() {
+            let p = factors[i];
+            let mut e = 0;
+            while i < factors.len() && factors[i] == p {
+                e += 1;
+                i += 1;
+            }
+            result.push((p, e));
+        }
+    }
+    result.sort_unstable();
+    result
+}
+
+/// `n` の約数を昇順で返す。
+///
+/// `n == 0` は `prime_factor` 経由で panic する。
+pub fn divisors(n: u64) -> Vec<u64> {
+    let mut divisors = vec![1u64];
+    for (p, e) in prime_factor(n) {
+        let mut pows = vec![1u64];
+        for _ in 0..e {
+            pows.push(pows[pows.len() - 1] * p);
+        }
+        let mut next = Vec::with_capacity(divisors.len() * pows.len());
+        for &d in &divisors {
+            for &pw in &pows {
+                next.push(d * pw);
+            }
+        }
+        divisors = next;
+    }
+    divisors.sort_unstable();
+    divisors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_divisors(n: u64) -> Vec<u64> {
+        let mut result = Vec::new();
+        let mut d = 1;
+        while d * d <= n {
+            if n.is_multiple_of(d) {
+                result.push(d);
+                if d != n / d {
+                    result.push(n / d);
+                }
+            }
+            d += 1;
+        }
+        result.sort_unstable();
+        result
+    }
+
+    #[test]
+    fn prime_factor_on_known_hard_cases() {
+        // かつて pollard_rho のバッチ gcd ステップが n に潰れてハングしていたケース。
+        assert_eq!(prime_factor(805675), vec![(5, 2), (13, 1), (37, 1), (67, 1)]);
+        assert_eq!(prime_factor(32227), vec![(13, 1), (37, 1), (67, 1)]);
+    }
+
+    #[test]
+    fn prime_factor_round_trip_over_random_sample() {
+        let mut next = crate::test_util::xorshift64(42);
+        for _ in 0..300 {
+            let n = next() % 1_000_000 + 1;
+            let factors = prime_factor(n);
+            let product: u64 = factors.iter().map(|&(p, e)| p.pow(e)).product();
+            assert_eq!(product, n, "prime_factor({}) = {:?}", n, factors);
+            for &(p, _) in &factors {
+                assert!(is_prime(p), "{} is not prime in factorization of {}", p, n);
+            }
+        }
+    }
+
+    #[test]
+    fn divisors_matches_naive_for_small_n() {
+        for n in 1..=2000u64 {
+            assert_eq!(divisors(n), naive_divisors(n), "mismatch for n={}", n);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "prime_factor(0) is undefined")]
+    fn prime_factor_zero_panics() {
+        prime_factor(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "prime_factor(0) is undefined")]
+    fn divisors_zero_panics() {
+        divisors(0);
+    }
+}
@@ -1,5 +1,9 @@
 pub mod modulus;
+pub mod convolution;
+pub mod precalc;
+pub mod factor;
 pub use modulus::Modulus;
+pub use precalc::Precalc;
 
 pub type ModInt998244353 = ModInt<u64, Mod998244353>;
 pub type ModInt1000000007 = ModInt<u64, Mod1000000007>;
@@ -107,6 +111,19 @@ impl<T, M: Modulus<T> + Default> std::convert::From<T> for ModInt<T, M> {
     }
 }
 
+/// テストで使う疑似乱数生成器（xorshift64）。各モジュールのテストで同じ実装を重複させないための共通ヘルパー。
+#[cfg(test)]
+pub(crate) mod test_util {
+    pub fn xorshift64(mut seed: u64) -> impl FnMut() -> u64 {
+        move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -40,6 +40,109 @@ pub trait Modulus<T>: PartialEq {
     }
 
     fn is_prime(&self) -> bool;
+
+    /// 法が素数であるとき、`y * y == x` となる `y` を返す（Tonelli-Shanks 法）。
+    /// `x` が平方剰余でなければ `None` を返す。
+    fn sqrt(&self, x: T) -> Option<T>
+    where
+        T: Clone
+            + PartialEq
+            + std::ops::Add<Output = T>
+            + std::ops::Rem<Output = T>
+            + std::ops::Div<Output = T>,
+    {
+        fn pow_raw<T, M: Modulus<T> + ?Sized>(m: &M, mut base: T, mut exp: T) -> T
+        where
+            T: Clone + PartialEq + std::ops::Rem<Output = T> + std::ops::Div<Output = T>,
+        {
+            let zero = m.zero();
+            let two = m.add(m.one(), m.one());
+            let mut result = m.one();
+            while exp != zero {
+                if exp.clone() % two.clone() != zero {
+                    result = m.mul(result, base.clone());
+                }
+                base = m.mul(base.clone(), base.clone());
+                exp = exp / two.clone();
+            }
+            result
+        }
+
+        let zero = self.zero();
+        if x == zero {
+            return Some(zero);
+        }
+        let one = self.one();
+        let two = self.add(one.clone(), one.clone());
+
+        // M == 2 のときはすべての元が自分自身の平方根であり、後段のループは `two` で割るため
+        // `two == zero` になって破綻する。`self.modulus()`（未還元）と `two`（還元済み）を
+        // 比較すると一致しないので、還元済みの値同士で判定する。
+        if two == zero {
+            return Some(x);
+        }
+
+        let three = self.add(two.clone(), one.clone());
+        let four = self.add(two.clone(), two.clone());
+
+        let m_minus_1 = self.neg(one.clone());
+
+        // オイラーの規準で非剰余を早期に棄却する。
+        if pow_raw(self, x.clone(), m_minus_1.clone() / two.clone()) == m_minus_1 {
+            return None;
+        }
+
+        if self.modulus() % four.clone() == three {
+            let y = pow_raw(self, x.clone(), (self.modulus() + one.clone()) / four);
+            return if self.mul(y.clone(), y.clone()) == x {
+                Some(y)
+            } else {
+                None
+            };
+        }
+
+        // M - 1 = q * 2^s と分解する。
+        let mut q = m_minus_1.clone();
+        let mut s: u32 = 0;
+        while q.clone() % two.clone() == zero {
+            q = q / two.clone();
+            s += 1;
+        }
+
+        // 平方非剰余 z を探す。
+        let mut z = two.clone();
+        while pow_raw(self, z.clone(), m_minus_1.clone() / two.clone()) != m_minus_1 {
+            z = self.add(z, one.clone());
+        }
+
+        let mut c = pow_raw(self, z, q.clone());
+        let mut t = pow_raw(self, x.clone(), q.clone());
+        let mut r = pow_raw(self, x.clone(), (q + one.clone()) / two);
+        let mut m_ladder = s;
+
+        while t != one {
+            let mut i = 0u32;
+            let mut temp = t.clone();
+            while temp != one {
+                temp = self.mul(temp.clone(), temp);
+                i += 1;
+            }
+            let mut b = c.clone();
+            for _ in 0..(m_ladder - i - 1) {
+                b = self.mul(b.clone(), b.clone());
+            }
+            r = self.mul(r, b.clone());
+            c = self.mul(b.clone(), b);
+            t = self.mul(t, c.clone());
+            m_ladder = i;
+        }
+
+        if self.mul(r.clone(), r.clone()) == x {
+            Some(r)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
@@ -120,21 +223,10 @@ impl<const M: u64> Modulus<u64> for StaticModulus64<M> {
     }
     
     fn inv(&self, x: u64) -> u64 {
-        assert!(x < M);
-        assert!(x != 0, "division by zero occured");
-        let mut s = (M as i64, 0);
-        let mut t = (x as i64, 1);
-        while t.0 != 0 {
-            let u = s.0 / t.0;
-            s.0 -= t.0 * u;
-            s.1 -= t.1 * u;
-            std::mem::swap(&mut s, &mut t);
-        }
-        assert!(s.0 == 1, "gcd({}, {}) = {}, which is not 1", x, M, s.1);
-        if s.1 < 0 {
-            s.1 += (M / s.0 as u64) as i64;
-        }
-        s.1 as u64
+        // M は i64::MAX を超える u64 定数も取りうるため、符号付き i64 にキャストせず、
+        // 符号を別途追跡する拡張ユークリッドの互除法で計算する `inv_u64` を使う
+        // （`BarrettModulus64::inv` と共有）。
+        inv_u64(M, x)
     }
     
     fn add(&self, x: u64, y: u64) -> u64 {
@@ -173,7 +265,7 @@ T: Clone +
 std::ops::Rem<Output = T> +
 Ord +
 std::convert::From<bool> +
-std::ops::Neg<Output = T> +
+std::ops::Sub<Output = T> +
 std::ops::Add<Output = T> +
 std::ops::Mul<Output = T> +
 std::ops::Div<Output = T>
@@ -185,35 +277,60 @@ std::ops::Div<Output = T>
     fn rem(&self, x: T) -> T {
         x % self.modulus()
     }
-    
+
     fn zero(&self) -> T {
         T::from(false)
     }
-    
+
     fn one(&self) -> T {
         T::from(true) % self.modulus()
     }
-    
+
     fn neg(&self, x: T) -> T {
-        -x % self.modulus()
+        if x == self.zero() {
+            self.zero()
+        } else {
+            self.modulus() - x
+        }
     }
-    
+
+    // 符号付きの値を経由せずに（`T` が符号なし整数でも使えるように）、
+    // Bezout 係数の符号を別途 bool で追跡しながら拡張ユークリッドの互除法を行う。
+    // `u64` 固定の `inv_u64` と同じ構造だが、`T` がジェネリックなためそちらは流用できない。
     fn inv(&self, x: T) -> T {
         assert!(x != self.zero(), "division by zero occured");
-        let mut s = (self.modulus(), self.zero());
-        let mut t = (x, self.one());
-        while t.0 != self.zero() {
-            let u = s.0.clone() / t.0.clone();
-            s.0 = s.0 + -t.0.clone() * u.clone();
-            s.1 = s.1 + -t.1.clone() * u.clone();
-            std::mem::swap(&mut s, &mut t);
+        let (mut r0, mut r1) = (self.modulus(), x);
+        let (mut t0, mut t0_neg) = (self.zero(), false);
+        let (mut t1, mut t1_neg) = (self.one(), false);
+        while r1 != self.zero() {
+            let q = r0.clone() / r1.clone();
+            let r2 = r0 - q.clone() * r1.clone();
+            r0 = r1;
+            r1 = r2;
+
+            let q_t1 = q * t1.clone();
+            let (t2, t2_neg) = if t0_neg == t1_neg {
+                if t0 >= q_t1 {
+                    (t0.clone() - q_t1.clone(), t0_neg)
+                } else {
+                    (q_t1.clone() - t0.clone(), !t0_neg)
+                }
+            } else {
+                (t0.clone() + q_t1.clone(), t0_neg)
+            };
+            t0 = t1;
+            t0_neg = t1_neg;
+            t1 = t2;
+            t1_neg = t2_neg;
         }
-        if s.1 < self.zero() {
-            s.1 = self.add(s.1, self.modulus() / s.0.clone());
+        assert!(r0 == self.one(), "gcd is not 1, value is not invertible");
+        if t0_neg {
+            self.modulus() - t0
+        } else {
+            t0
         }
-        s.1
     }
-    
+
     fn add(&self, x: T, y: T) -> T {
         (x + y) % self.0.clone()
     }
@@ -237,20 +354,134 @@ std::ops::Div<Output = T>
     }
 }
 
-const fn is_prime(n: u64) -> bool {
+/// `DynamicModulus<u64>` の代わりに使える、Barrett 簡約によるモジュラスの実装。
+/// `(x * y) % m` を行うたびにハードウェア除算を行う代わりに、構築時に求めた
+/// `mu = floor(2^128 / m)` を使って乗算と条件付き減算のみで剰余を求める。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BarrettModulus64 {
+    m: u64,
+    mu: u128,
+}
+
+impl BarrettModulus64 {
+    pub fn new(m: u64) -> Self {
+        assert!(m > 1, "modulus must be greater than 1");
+        let m128 = m as u128;
+        let q = u128::MAX / m128;
+        let r = u128::MAX % m128;
+        let mu = if r == m128 - 1 { q + 1 } else { q };
+        Self { m, mu }
+    }
+
+    /// `a * b` の 256 ビット積のうち、上位 128 ビットを返す。
+    fn mulhi(a: u128, b: u128) -> u128 {
+        let a_lo = a as u64 as u128;
+        let a_hi = a >> 64;
+        let b_lo = b as u64 as u128;
+        let b_hi = b >> 64;
+
+        let lo_lo = a_lo * b_lo;
+        let hi_lo = a_hi * b_lo;
+        let lo_hi = a_lo * b_hi;
+        let hi_hi = a_hi * b_hi;
+
+        let cross = (lo_lo >> 64) + (hi_lo as u64 as u128) + (lo_hi as u64 as u128);
+        let carry = (hi_lo >> 64) + (lo_hi >> 64) + (cross >> 64);
+        hi_hi + carry
+    }
+
+    /// `0 <= x < m * m` を満たす `x` を `mod m` に簡約する。
+    fn reduce(&self, x: u128) -> u64 {
+        let m128 = self.m as u128;
+        let q = Self::mulhi(x, self.mu);
+        let mut r = x - q * m128;
+        if r >= m128 {
+            r -= m128;
+        }
+        if r >= m128 {
+            r -= m128;
+        }
+        r as u64
+    }
+}
+
+impl Modulus<u64> for BarrettModulus64 {
+    fn modulus(&self) -> u64 {
+        self.m
+    }
+
+    fn rem(&self, x: u64) -> u64 {
+        x % self.m
+    }
+
+    fn zero(&self) -> u64 {
+        0
+    }
+
+    fn one(&self) -> u64 {
+        1
+    }
+
+    fn neg(&self, x: u64) -> u64 {
+        assert!(x < self.m);
+        if x == 0 {
+            0
+        } else {
+            self.m - x
+        }
+    }
+
+    fn inv(&self, x: u64) -> u64 {
+        // self.m は i64::MAX を超える値も取りうるため、
+        // `StaticModulus64::inv` と共有する `inv_u64` で符号追跡つきの拡張ユークリッドを行う。
+        inv_u64(self.m, x)
+    }
+
+    fn add(&self, x: u64, y: u64) -> u64 {
+        assert!(x < self.m && y < self.m);
+        let mut z = x as u128 + y as u128;
+        if z >= self.m as u128 {
+            z -= self.m as u128;
+        }
+        z as u64
+    }
+
+    fn mul(&self, x: u64, y: u64) -> u64 {
+        assert!(x < self.m && y < self.m);
+        self.reduce(x as u128 * y as u128)
+    }
+
+    fn is_prime(&self) -> bool {
+        is_prime(self.m)
+    }
+}
+
+// {2,3,5,7,11,13,17,19,23,29,31,37} は n < 3.3 * 10^24（u64 の全域をカバーする）に対して
+// 決定的な Miller-Rabin の証人集合である。
+pub(crate) const fn is_prime(n: u64) -> bool {
     if n <= 1 {
         return false;
     }
-    if n == 2 || n == 7 || n == 61 {
-        return true;
+    let witnesses = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    {
+        let mut i = 0;
+        while i < witnesses.len() {
+            if n == witnesses[i] {
+                return true;
+            }
+            i += 1;
+        }
     }
     if n & 1 == 0 {
         return false;
     }
     let d = (n - 1) >> (n - 1).trailing_zeros();
-    let witnesses = [2, 7, 61];
     let mut i = 0;
     while i < witnesses.len() {
+        if witnesses[i] >= n {
+            i += 1;
+            continue;
+        }
         let mut t = d;
         let mut y = mod_pow(witnesses[i], t, n);
         while t != n - 1 && y != 1 && y != n - 1 {
@@ -265,7 +496,7 @@ const fn is_prime(n: u64) -> bool {
     true
 }
 
-const fn mod_pow(mut x: u64, mut y: u64, m: u64) -> u64 {
+pub(crate) const fn mod_pow(mut x: u64, mut y: u64, m: u64) -> u64 {
     let mut z = 1;
     while y != 0 {
         if y & 1 != 0 {
@@ -275,4 +506,139 @@ const fn mod_pow(mut x: u64, mut y: u64, m: u64) -> u64 {
         y >>= 1;
     }
     z
+}
+
+/// `x` の `mod m` における逆元を返す。`m` は `i64::MAX` を超える値も取りうるため、
+/// 符号付き整数にキャストせず、符号を別途追跡する拡張ユークリッドの互除法で計算する。
+/// `StaticModulus64::inv` と `BarrettModulus64::inv` から共有される
+/// （`DynamicModulus::inv` も同じ構造を `T: Clone + ...` 上でジェネリックに再実装している）。
+pub(crate) const fn inv_u64(m: u64, x: u64) -> u64 {
+    assert!(x < m);
+    assert!(x != 0, "division by zero occured");
+    let (mut r0, mut r1) = (m, x);
+    let (mut t0, mut t0_neg) = (0u64, false);
+    let (mut t1, mut t1_neg) = (1u64, false);
+    while r1 != 0 {
+        let q = r0 / r1;
+        let r2 = r0 - q * r1;
+        r0 = r1;
+        r1 = r2;
+
+        let q_t1 = q * t1;
+        let (t2, t2_neg) = if t0_neg == t1_neg {
+            if t0 >= q_t1 {
+                (t0 - q_t1, t0_neg)
+            } else {
+                (q_t1 - t0, !t0_neg)
+            }
+        } else {
+            (t0 + q_t1, t0_neg)
+        };
+        t0 = t1;
+        t0_neg = t1_neg;
+        t1 = t2;
+        t1_neg = t2_neg;
+    }
+    assert!(r0 == 1, "gcd is not 1, value is not invertible");
+    if t0_neg {
+        m - t0
+    } else {
+        t0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn barrett_matches_dynamic_modulus() {
+        // `DynamicModulus<u64>::mul` は `m` が 32 ビットに収まらなくなるとオーバーフローするため、
+        // 素の `x * y` が安全な法でのみ比較のリファレンスとして使う。
+        let moduli = [2u64, 3, 1_000_000_007, 998_244_353, 4_294_967_291, u64::MAX];
+        let mut next = crate::test_util::xorshift64(88172645463325252);
+        fn gcd(a: u64, b: u64) -> u64 {
+            if b == 0 {
+                a
+            } else {
+                gcd(b, a % b)
+            }
+        }
+
+        for &m in &moduli {
+            let barrett = BarrettModulus64::new(m);
+            let dynamic = DynamicModulus::new(m);
+            for _ in 0..1000 {
+                let x = next() % m;
+                let y = next() % m;
+                assert_eq!(
+                    barrett.mul(x, y),
+                    (x as u128 * y as u128 % m as u128) as u64
+                );
+                if m <= (1u64 << 32) {
+                    assert_eq!(barrett.add(x, y), dynamic.add(x, y));
+                    assert_eq!(barrett.mul(x, y), dynamic.mul(x, y));
+                }
+                // i64::MAX を大きく超える法（上の `u64::MAX` など）でも `inv` が正しく動くことを確認する。
+                if x != 0 && gcd(x, m) == 1 {
+                    let x_inv = barrett.inv(x);
+                    assert_eq!(barrett.mul(x, x_inv), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn is_prime_pins_known_strong_pseudoprimes_and_large_primes() {
+        // 底 {2,3,5,7} と底 {2,3,5,7,11,13,17} それぞれに対する最小の強擬素数。
+        // {2,...,37} のすべての底を使えば合成数と判定される。
+        assert!(!is_prime(3_215_031_751));
+        assert!(!is_prime(341_550_071_728_321));
+        // u64::MAX 付近の大きな素数。
+        assert!(is_prime(18_446_744_073_709_551_557));
+        assert!(is_prime(1_000_000_007));
+        assert!(is_prime(998_244_353));
+    }
+
+    #[test]
+    fn sqrt_handles_modulus_two() {
+        let modulus = StaticModulus64::<2>;
+        assert_eq!(modulus.sqrt(0), Some(0));
+        assert_eq!(modulus.sqrt(1), Some(1));
+    }
+
+    #[test]
+    fn sqrt_matches_brute_force_for_small_primes() {
+        // M % 4 == 3（高速経路）と M % 4 == 1（一般ループ）の両方の分岐を確認する。
+        for m in [5u64, 7, 11, 13, 17, 19, 23, 29, 37, 41, 97, 257] {
+            let modulus = DynamicModulus::new(m);
+            for x in 0..m {
+                let expect = (0..m).find(|&y| y * y % m == x);
+                let got = modulus.sqrt(x);
+                match got {
+                    Some(y) => assert_eq!(y * y % m, x, "sqrt({}) mod {} = {} is wrong", x, m, y),
+                    None => assert_eq!(expect, None, "sqrt({}) mod {} should be {:?}", x, m, expect),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sqrt_round_trips_for_large_static_moduli() {
+        // 998244353 % 4 == 1（一般ループ）、1000000007 % 4 == 3（高速経路）。
+        let mut next = crate::test_util::xorshift64(123456789);
+        for _ in 0..2000 {
+            let x998 = next() % 998244353;
+            let m998 = Mod998244353::default();
+            if let Some(y) = m998.sqrt(x998) {
+                assert_eq!(m998.mul(y, y), x998);
+            }
+
+            let x1e9 = next() % 1000000007;
+            let m1e9 = Mod1000000007::default();
+            if let Some(y) = m1e9.sqrt(x1e9) {
+                assert_eq!(m1e9.mul(y, y), x1e9);
+            }
+        }
+    }
 }
\ No newline at end of file
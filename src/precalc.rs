@@ -0,0 +1,134 @@
+//! 階乗・二項係数の前計算。
+
+use crate::{ModInt, Modulus};
+
+/// `fact`, `ifact`, `inv` を必要に応じて伸長しながら保持し、
+/// 階乗・逆元・二項係数・順列数を O(1)、多項係数を `ks` の長さに比例する時間
+/// （いずれも伸長時は伸長分だけ O(n) が加わる）で返す。
+pub struct Precalc<M: Modulus<u64> + Default + Copy> {
+    fact: Vec<ModInt<u64, M>>,
+    ifact: Vec<ModInt<u64, M>>,
+    inv: Vec<ModInt<u64, M>>,
+}
+
+impl<M: Modulus<u64> + Default + Copy> Precalc<M> {
+    pub fn new() -> Self {
+        Self {
+            fact: vec![ModInt::one()],
+            ifact: vec![ModInt::one()],
+            inv: vec![ModInt::zero()],
+        }
+    }
+
+    pub fn with_capacity(n: usize) -> Self {
+        let mut precalc = Self::new();
+        precalc.extend(n);
+        precalc
+    }
+
+    fn extend(&mut self, n: usize) {
+        let len = self.fact.len();
+        if n < len {
+            return;
+        }
+        self.fact.reserve(n + 1 - len);
+        for i in len..=n {
+            let prev = self.fact[i - 1];
+            self.fact.push(prev * i as u64);
+        }
+        self.ifact.resize(n + 1, ModInt::one());
+        self.ifact[n] = self.fact[n].inv();
+        for i in (len..n).rev() {
+            self.ifact[i] = self.ifact[i + 1] * (i as u64 + 1);
+        }
+        self.inv.resize(n + 1, ModInt::zero());
+        for i in len.max(1)..=n {
+            self.inv[i] = self.ifact[i] * self.fact[i - 1];
+        }
+    }
+
+    pub fn fact(&mut self, n: usize) -> ModInt<u64, M> {
+        self.extend(n);
+        self.fact[n]
+    }
+
+    pub fn ifact(&mut self, n: usize) -> ModInt<u64, M> {
+        self.extend(n);
+        self.ifact[n]
+    }
+
+    pub fn inv(&mut self, n: usize) -> ModInt<u64, M> {
+        assert!(n != 0, "Precalc::inv(0) is undefined");
+        self.extend(n);
+        self.inv[n]
+    }
+
+    pub fn perm(&mut self, n: usize, k: usize) -> ModInt<u64, M> {
+        if k > n {
+            return ModInt::zero();
+        }
+        self.extend(n);
+        self.fact[n] * self.ifact[n - k]
+    }
+
+    pub fn binom(&mut self, n: usize, k: usize) -> ModInt<u64, M> {
+        if k > n {
+            return ModInt::zero();
+        }
+        self.extend(n);
+        self.fact[n] * self.ifact[k] * self.ifact[n - k]
+    }
+
+    pub fn multinomial(&mut self, ks: &[usize]) -> ModInt<u64, M> {
+        let n: usize = ks.iter().sum();
+        self.extend(n);
+        let mut z = self.fact[n];
+        for &k in ks {
+            z *= self.ifact[k];
+        }
+        z
+    }
+}
+
+impl<M: Modulus<u64> + Default + Copy> Default for Precalc<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modulus::StaticModulus64;
+
+    type M = StaticModulus64<998244353>;
+
+    #[test]
+    fn binom_matches_hand_computed_values() {
+        let mut precalc = Precalc::<M>::new();
+        assert_eq!(precalc.binom(5, 2), ModInt::from(10u64));
+        assert_eq!(precalc.binom(10, 0), ModInt::from(1u64));
+        assert_eq!(precalc.binom(10, 10), ModInt::from(1u64));
+        assert_eq!(precalc.binom(3, 5), ModInt::from(0u64));
+    }
+
+    #[test]
+    fn perm_matches_hand_computed_values() {
+        let mut precalc = Precalc::<M>::new();
+        assert_eq!(precalc.perm(5, 2), ModInt::from(20u64));
+        assert_eq!(precalc.perm(3, 5), ModInt::from(0u64));
+    }
+
+    #[test]
+    fn multinomial_matches_hand_computed_value() {
+        let mut precalc = Precalc::<M>::new();
+        // 5! / (2! * 3!) = 10
+        assert_eq!(precalc.multinomial(&[2, 3]), ModInt::from(10u64));
+    }
+
+    #[test]
+    #[should_panic(expected = "Precalc::inv(0) is undefined")]
+    fn inv_zero_panics() {
+        Precalc::<M>::new().inv(0);
+    }
+}